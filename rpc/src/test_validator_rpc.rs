@@ -17,9 +17,343 @@ use solana_sdk::{account::AccountSharedData, clock::Clock, hash::Hash, program_p
 use solana_runtime::accounts_background_service::{AbsRequestSender, SnapshotRequestKind};
 use crate::rpc::JsonRpcRequestProcessor;
 use spl_token::state::{Account as TokenAccount, AccountState};
+use spl_token_2022::extension::{StateWithExtensions, StateWithExtensionsMut};
 use solana_sdk::account::ReadableAccount;
 // use solana_core::consensus::progress_map::{ForkProgress, ProgressMap};
 
+// Mirrors the mainline RPC's `is_known_spl_token_id`: both the classic SPL
+// Token program and Token-2022 share the same base account layout, so we treat
+// either owner as a token account and branch on the extension parsing.
+fn is_known_spl_token_id(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == spl_token_2022::id()
+}
+
+// Read a token account's `amount`, parsing Token-2022 accounts through the
+// extension layout so accounts carrying TLV extensions don't fail to unpack.
+fn token_account_amount(data: &[u8], owner: &Pubkey) -> Result<u64> {
+    if *owner == spl_token_2022::id() {
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(data)
+            .map(|state| state.base.amount)
+            .map_err(|e| Error::invalid_params(format!("Invalid token account data: {}", e)))
+    } else {
+        TokenAccount::unpack(data)
+            .map(|account| account.amount)
+            .map_err(|e| Error::invalid_params(format!("Invalid token account data: {}", e)))
+    }
+}
+
+// Reconcile a mint's supply with a token balance change, erroring rather than
+// silently clamping if the delta would drive the supply out of the u64 range.
+fn reconcile_supply(current: u64, amount: u64, previous: u64) -> Result<u64> {
+    let next = current as i128 + amount as i128 - previous as i128;
+    if next < 0 {
+        return Err(Error::invalid_params(format!(
+            "Balance change would drive mint supply negative (supply {}, delta {})",
+            current,
+            amount as i128 - previous as i128
+        )));
+    }
+    u64::try_from(next)
+        .map_err(|_| Error::invalid_params("Mint supply would exceed u64::MAX".to_string()))
+}
+
+// JSON field accessors for reversing `jsonParsed` account data.
+fn parsed_str<'a>(info: &'a serde_json::Value, key: &str) -> Result<&'a str> {
+    info.get(key)
+        .and_then(|value| value.as_str())
+        .ok_or_else(|| Error::invalid_params(format!("Missing `{}` in parsed account", key)))
+}
+
+fn parsed_pubkey(info: &serde_json::Value, key: &str) -> Result<Pubkey> {
+    use std::str::FromStr;
+    Pubkey::from_str(parsed_str(info, key)?)
+        .map_err(|e| Error::invalid_params(format!("Invalid `{}` pubkey: {}", key, e)))
+}
+
+// Accept either a JSON number or a decimal string (token amounts and supplies
+// are emitted as strings to preserve u64 precision).
+fn parsed_u64(info: &serde_json::Value, key: &str) -> Result<u64> {
+    let value = info
+        .get(key)
+        .ok_or_else(|| Error::invalid_params(format!("Missing `{}` in parsed account", key)))?;
+    if let Some(n) = value.as_u64() {
+        Ok(n)
+    } else if let Some(s) = value.as_str() {
+        s.parse::<u64>()
+            .map_err(|e| Error::invalid_params(format!("Invalid `{}` amount: {}", key, e)))
+    } else {
+        Err(Error::invalid_params(format!("Invalid `{}` amount", key)))
+    }
+}
+
+// Re-pack a `jsonParsed` payload into raw account data, reversing the
+// account-decoder for the token, stake and nonce programs. The result is padded
+// to the parsed `space` so it matches the on-chain account length.
+fn repack_parsed_account(
+    parsed: &solana_account_decoder::parse_account_data::ParsedAccount,
+) -> Result<Vec<u8>> {
+    let info = parsed
+        .parsed
+        .get("info")
+        .ok_or_else(|| Error::invalid_params("Parsed account is missing `info`".to_string()))?;
+    let account_type = parsed.parsed.get("type").and_then(|t| t.as_str());
+
+    let mut data = match parsed.program.as_str() {
+        "spl-token" | "spl-token-2022" => repack_parsed_token(info, account_type)?,
+        "stake" => repack_parsed_stake(info, account_type)?,
+        "nonce" => repack_parsed_nonce(info)?,
+        other => {
+            return Err(Error::invalid_params(format!(
+                "jsonParsed reconstruction is not supported for program `{}`",
+                other
+            )))
+        }
+    };
+
+    // jsonParsed does not carry the raw TLV extension bytes, so a Token-2022
+    // account that declares extension space beyond the base cannot be rebuilt
+    // here — zero-padding would leave the account-type byte and TLV region
+    // malformed. Reject it so the caller re-fetches with a binary encoding.
+    if matches!(parsed.program.as_str(), "spl-token" | "spl-token-2022")
+        && parsed.space > data.len() as u64
+    {
+        return Err(Error::invalid_params(format!(
+            "Cannot reconstruct a Token-2022 account with {} bytes of extension data \
+             from jsonParsed; request it with base64/base64+zstd encoding instead",
+            parsed.space - data.len() as u64
+        )));
+    }
+
+    // Match the declared on-chain length, zero-padding any trailing bytes.
+    if (data.len() as u64) < parsed.space {
+        data.resize(parsed.space as usize, 0);
+    }
+    Ok(data)
+}
+
+fn repack_parsed_token(info: &serde_json::Value, account_type: Option<&str>) -> Result<Vec<u8>> {
+    use spl_token::state::Mint as TokenMint;
+
+    match account_type {
+        Some("account") => {
+            let token_amount = info.get("tokenAmount").ok_or_else(|| {
+                Error::invalid_params("Missing `tokenAmount` in parsed token account".to_string())
+            })?;
+            let state = match parsed_str(info, "state")? {
+                "uninitialized" => AccountState::Uninitialized,
+                "initialized" => AccountState::Initialized,
+                "frozen" => AccountState::Frozen,
+                other => {
+                    return Err(Error::invalid_params(format!(
+                        "Unknown token account state `{}`",
+                        other
+                    )))
+                }
+            };
+            let delegate = match info.get("delegate") {
+                Some(_) => Some(parsed_pubkey(info, "delegate")?).into(),
+                None => None.into(),
+            };
+            let delegated_amount = info
+                .get("delegatedAmount")
+                .map(|amount| {
+                    amount
+                        .get("amount")
+                        .and_then(|a| a.as_str())
+                        .unwrap_or("0")
+                        .parse::<u64>()
+                        .unwrap_or(0)
+                })
+                .unwrap_or(0);
+            // A native (wrapped-SOL) account's `is_native` COption holds the
+            // rent-exempt reserve, which jsonParsed emits as `rentExemptReserve`.
+            let is_native = if info.get("isNative").and_then(|n| n.as_bool()) == Some(true) {
+                let reserve = info
+                    .get("rentExemptReserve")
+                    .map(|reserve| parsed_u64(reserve, "amount"))
+                    .transpose()?
+                    .unwrap_or(0);
+                Some(reserve).into()
+            } else {
+                None.into()
+            };
+            let close_authority = match info.get("closeAuthority") {
+                Some(_) => Some(parsed_pubkey(info, "closeAuthority")?).into(),
+                None => None.into(),
+            };
+
+            let token_state = TokenAccount {
+                mint: parsed_pubkey(info, "mint")?,
+                owner: parsed_pubkey(info, "owner")?,
+                amount: parsed_u64(token_amount, "amount")?,
+                delegate,
+                state,
+                is_native,
+                delegated_amount,
+                close_authority,
+            };
+            let mut data = vec![0; TokenAccount::get_packed_len()];
+            TokenAccount::pack(token_state, &mut data).map_err(|e| {
+                Error::invalid_params(format!("Failed to pack token account data: {}", e))
+            })?;
+            Ok(data)
+        }
+        Some("mint") => {
+            let mint_authority = match info.get("mintAuthority") {
+                Some(value) if !value.is_null() => Some(parsed_pubkey(info, "mintAuthority")?).into(),
+                _ => None.into(),
+            };
+            let freeze_authority = match info.get("freezeAuthority") {
+                Some(value) if !value.is_null() => {
+                    Some(parsed_pubkey(info, "freezeAuthority")?).into()
+                }
+                _ => None.into(),
+            };
+            let mint_state = TokenMint {
+                mint_authority,
+                supply: parsed_u64(info, "supply")?,
+                decimals: parsed_u64(info, "decimals")? as u8,
+                is_initialized: info
+                    .get("isInitialized")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true),
+                freeze_authority,
+            };
+            let mut data = vec![0; TokenMint::get_packed_len()];
+            TokenMint::pack(mint_state, &mut data)
+                .map_err(|e| Error::invalid_params(format!("Failed to pack mint data: {}", e)))?;
+            Ok(data)
+        }
+        other => Err(Error::invalid_params(format!(
+            "Unsupported parsed token account type `{:?}`",
+            other
+        ))),
+    }
+}
+
+fn repack_parsed_stake(info: &serde_json::Value, account_type: Option<&str>) -> Result<Vec<u8>> {
+    use solana_sdk::stake::state::{
+        Authorized, Delegation, Lockup, Meta, Stake, StakeStateV2,
+    };
+
+    let read_meta = |meta: &serde_json::Value| -> Result<Meta> {
+        let authorized = meta.get("authorized").ok_or_else(|| {
+            Error::invalid_params("Missing `authorized` in parsed stake account".to_string())
+        })?;
+        let lockup = meta.get("lockup").ok_or_else(|| {
+            Error::invalid_params("Missing `lockup` in parsed stake account".to_string())
+        })?;
+        Ok(Meta {
+            rent_exempt_reserve: parsed_u64(meta, "rentExemptReserve")?,
+            authorized: Authorized {
+                staker: parsed_pubkey(authorized, "staker")?,
+                withdrawer: parsed_pubkey(authorized, "withdrawer")?,
+            },
+            lockup: Lockup {
+                unix_timestamp: parsed_u64(lockup, "unixTimestamp")? as i64,
+                epoch: parsed_u64(lockup, "epoch")?,
+                custodian: parsed_pubkey(lockup, "custodian")?,
+            },
+        })
+    };
+
+    let read_stake = |stake: &serde_json::Value| -> Result<Stake> {
+        let delegation = stake.get("delegation").ok_or_else(|| {
+            Error::invalid_params("Missing `delegation` in parsed stake account".to_string())
+        })?;
+        Ok(Stake {
+            delegation: Delegation {
+                voter_pubkey: parsed_pubkey(delegation, "voter")?,
+                stake: parsed_u64(delegation, "stake")?,
+                activation_epoch: parsed_u64(delegation, "activationEpoch")?,
+                deactivation_epoch: parsed_u64(delegation, "deactivationEpoch")?,
+                ..Delegation::default()
+            },
+            credits_observed: parsed_u64(stake, "creditsObserved")?,
+        })
+    };
+
+    let state = match account_type {
+        Some("uninitialized") => StakeStateV2::Uninitialized,
+        Some("initialized") => {
+            let meta = info.get("meta").ok_or_else(|| {
+                Error::invalid_params("Missing `meta` in parsed stake account".to_string())
+            })?;
+            StakeStateV2::Initialized(read_meta(meta)?)
+        }
+        Some("delegated") => {
+            let meta = info.get("meta").ok_or_else(|| {
+                Error::invalid_params("Missing `meta` in parsed stake account".to_string())
+            })?;
+            let stake = info.get("stake").ok_or_else(|| {
+                Error::invalid_params("Missing `stake` in parsed stake account".to_string())
+            })?;
+            StakeStateV2::Stake(read_meta(meta)?, read_stake(stake)?, Default::default())
+        }
+        other => {
+            return Err(Error::invalid_params(format!(
+                "Unsupported parsed stake account type `{:?}`",
+                other
+            )))
+        }
+    };
+
+    bincode::serialize(&state)
+        .map_err(|e| Error::invalid_params(format!("Failed to serialize stake state: {}", e)))
+}
+
+fn repack_parsed_nonce(info: &serde_json::Value) -> Result<Vec<u8>> {
+    use solana_sdk::nonce::state::{Data, DurableNonce, State, Versions};
+    use solana_sdk::hash::Hash;
+    use std::str::FromStr;
+
+    let authority = parsed_pubkey(info, "authority")?;
+    let blockhash = Hash::from_str(parsed_str(info, "blockhash")?)
+        .map_err(|e| Error::invalid_params(format!("Invalid nonce blockhash: {}", e)))?;
+    let fee_calculator = info.get("feeCalculator").ok_or_else(|| {
+        Error::invalid_params("Missing `feeCalculator` in parsed nonce account".to_string())
+    })?;
+    let lamports_per_signature = parsed_u64(fee_calculator, "lamportsPerSignature")?;
+
+    let data = Data::new(authority, DurableNonce::from_blockhash(&blockhash), lamports_per_signature);
+    let versions = Versions::new(State::Initialized(data));
+
+    bincode::serialize(&versions)
+        .map_err(|e| Error::invalid_params(format!("Failed to serialize nonce state: {}", e)))
+}
+
+// Reverse the binary encodings `UiAccount::encode` emits: base58/base64 for raw
+// bytes and base64+zstd for compressed payloads. Used by the set-account paths
+// that accept account data straight off the wire.
+fn decode_account_data(
+    data: &str,
+    encoding: solana_account_decoder::UiAccountEncoding,
+) -> Result<Vec<u8>> {
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use solana_account_decoder::UiAccountEncoding;
+
+    match encoding {
+        UiAccountEncoding::Base58 => bs58::decode(data)
+            .into_vec()
+            .map_err(|e| Error::invalid_params(format!("Invalid base58 account data: {}", e))),
+        UiAccountEncoding::Base64 => BASE64_STANDARD
+            .decode(data)
+            .map_err(|e| Error::invalid_params(format!("Invalid base64 account data: {}", e))),
+        UiAccountEncoding::Base64Zstd => {
+            let compressed = BASE64_STANDARD.decode(data).map_err(|e| {
+                Error::invalid_params(format!("Invalid base64 account data: {}", e))
+            })?;
+            zstd::stream::decode_all(compressed.as_slice()).map_err(|e| {
+                Error::invalid_params(format!("Failed to zstd-decompress account data: {}", e))
+            })
+        }
+        other => Err(Error::invalid_params(format!(
+            "Unsupported account data encoding: {:?}",
+            other
+        ))),
+    }
+}
+
 // Allow automatic forwarding of method calls to the base implementation
 impl Deref for TestValidatorJsonRpcRequestProcessor {
     type Target = JsonRpcRequestProcessor;
@@ -38,6 +372,21 @@ pub struct TestValidatorJsonRpcRequestProcessor {
 
 impl TestValidatorJsonRpcRequestProcessor {
     pub fn warp_slot_impl(&self, target_slot: u64) -> Result<()> {
+        self.warp_slot_with_clock_impl(target_slot, false, None)
+    }
+
+    // Warp to `target_slot`, optionally advancing the `Clock`. When
+    // `advance_clock` is set, the `Clock` is recomputed (reusing
+    // `set_clock_impl`'s epoch math) from the pre-warp timestamp plus
+    // `seconds_per_slot` (default ~0.4s) times the number of skipped slots. Any
+    // epoch-boundary stake-reward and sysvar updates are performed by the
+    // `warp_from_parent`/`new_from_parent` constructors used to build the banks.
+    pub fn warp_slot_with_clock_impl(
+        &self,
+        target_slot: u64,
+        advance_clock: bool,
+        seconds_per_slot: Option<f64>,
+    ) -> Result<()> {
         let mut bank_forks = self.bank_forks.write().unwrap();
         let bank = bank_forks.working_bank();
         let mut poh_recorder = self.poh_recorder.write().unwrap();
@@ -46,6 +395,8 @@ impl TestValidatorJsonRpcRequestProcessor {
         if target_slot <= working_slot {
             return Err(Error::from(RpcCustomError::InvalidWarpSlot));
         }
+        // Capture the pre-warp clock so the new timestamp can be derived from it.
+        let pre_warp_timestamp = bank.clock().unix_timestamp;
         let pre_warp_slot = target_slot - 1;
         let warp_bank = if pre_warp_slot == working_slot {
             bank.freeze();
@@ -65,6 +416,11 @@ impl TestValidatorJsonRpcRequestProcessor {
         bank_forks
             .set_root(pre_warp_slot, &abs_request_sender, Some(pre_warp_slot))
             .unwrap();
+        // Always drain any queued snapshot requests and finalize the
+        // epoch-accounts-hash: `set_root` enqueues an EAH request when the
+        // rooted slot crosses the EAH calculation-start slot (~25% into an
+        // epoch), not on the epoch boundary itself, so this cannot be gated on
+        // the epoch number without risking an un-finalized manager.
         snapshot_request_receiver
             .try_iter()
             .filter(|snapshot_request| {
@@ -103,16 +459,205 @@ impl TestValidatorJsonRpcRequestProcessor {
         poh_recorder.resume();
         let mut w_block_commitment_cache = self.block_commitment_cache.write().unwrap();
         w_block_commitment_cache.set_all_slots(target_slot, target_slot);
+
+        if advance_clock {
+            // Advance the wall-clock by the time the skipped slots would have
+            // taken. Drop the locks first: `set_clock_impl` re-acquires
+            // `bank_forks` for reading and operates on the new working bank.
+            drop(w_block_commitment_cache);
+            drop(poh_recorder);
+            drop(bank_forks);
+
+            let seconds_per_slot = seconds_per_slot.unwrap_or(
+                solana_sdk::clock::DEFAULT_MS_PER_SLOT as f64 / 1_000.0,
+            );
+            let elapsed = (seconds_per_slot * (target_slot - working_slot) as f64) as i64;
+            let unix_timestamp = pre_warp_timestamp.saturating_add(elapsed);
+            self.set_clock_impl(target_slot, unix_timestamp, None);
+        }
+
         Ok(())
     }
 
-    // pub fn update_token_balance(&self, target_slot: u64) -> Result<()> {
-    //     let mut bank_forks = self.bank_forks.write().unwrap();
-    //     let bank = bank_forks.working_bank();
-    //     let mut poh_recorder = self.poh_recorder.write().unwrap();
-    //     poh_recorder.pause();
-        
-    // }
+    // Fund an arbitrary wallet in a single call, without issuing real
+    // mint/transfer instructions. Ensures `mint` exists (creating a rent-exempt
+    // `Mint` with `decimals`, default 6, and `mint_authority`/`freeze_authority`
+    // set to `owner`), then creates or updates `token_account` to hold `amount`
+    // while preserving every other field — including Token-2022 TLV extensions.
+    // The mint `supply` is kept consistent by the balance delta so a subsequent
+    // `getTokenSupply` reflects reality.
+    pub fn update_token_balance(
+        &self,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+        decimals: Option<u8>,
+    ) -> Result<()> {
+        use spl_token::state::Mint as TokenMint;
+
+        let bank_forks = self.bank_forks.read().unwrap();
+        let bank = bank_forks.working_bank();
+
+        // Resolve which token program to use for any account we have to create:
+        // prefer the existing token account's owner, then the mint's owner, else
+        // classic SPL Token.
+        let token_program_id = bank
+            .get_account(token_account)
+            .map(|account| *account.owner())
+            .filter(is_known_spl_token_id)
+            .or_else(|| {
+                bank.get_account(mint)
+                    .map(|account| *account.owner())
+                    .filter(is_known_spl_token_id)
+            })
+            .unwrap_or_else(spl_token::id);
+
+        // Record the balance the token account held before this update so the
+        // mint supply can be adjusted by the delta.
+        let previous_amount = match bank.get_account(token_account) {
+            Some(existing_account) if is_known_spl_token_id(existing_account.owner()) => {
+                token_account_amount(existing_account.data(), existing_account.owner())?
+            }
+            _ => 0,
+        };
+
+        // Ensure the mint exists, then reconcile its supply with the new balance.
+        match bank.get_account(mint) {
+            Some(mint_account) if *mint_account.owner() == spl_token_2022::id() => {
+                // Patch the base `supply` in place, preserving TLV extensions.
+                let mut data = mint_account.data().to_vec();
+                {
+                    let mut state =
+                        StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack(&mut data)
+                            .map_err(|e| {
+                                Error::invalid_params(format!("Invalid mint data: {}", e))
+                            })?;
+                    state.base.supply =
+                        reconcile_supply(state.base.supply, amount, previous_amount)?;
+                    state.pack_base();
+                }
+                let mut account = AccountSharedData::new(
+                    mint_account.lamports(),
+                    data.len(),
+                    mint_account.owner(),
+                );
+                account.set_data(data);
+                bank.store_account(mint, &account);
+            }
+            Some(mint_account) => {
+                let mut mint_data = TokenMint::unpack(mint_account.data())
+                    .map_err(|e| Error::invalid_params(format!("Invalid mint data: {}", e)))?;
+                mint_data.supply = reconcile_supply(mint_data.supply, amount, previous_amount)?;
+
+                let mut data = vec![0; TokenMint::get_packed_len()];
+                TokenMint::pack(mint_data, &mut data).map_err(|e| {
+                    Error::invalid_params(format!("Failed to pack mint data: {}", e))
+                })?;
+
+                let mut account = AccountSharedData::new(
+                    mint_account.lamports(),
+                    data.len(),
+                    mint_account.owner(),
+                );
+                account.set_data(data);
+                bank.store_account(mint, &account);
+            }
+            None => {
+                // Start from an empty supply and apply the same delta as the
+                // other arms, so a pre-existing balance against a not-yet-created
+                // mint doesn't leave supply inconsistent with total balances.
+                let mint_state = TokenMint {
+                    mint_authority: Some(*owner).into(),
+                    supply: reconcile_supply(0, amount, previous_amount)?,
+                    decimals: decimals.unwrap_or(6),
+                    is_initialized: true,
+                    freeze_authority: Some(*owner).into(),
+                };
+                let mint_data_size = TokenMint::get_packed_len();
+                let mut data = vec![0; mint_data_size];
+                TokenMint::pack(mint_state, &mut data).map_err(|e| {
+                    Error::invalid_params(format!("Failed to pack mint data: {}", e))
+                })?;
+
+                let mut account = AccountSharedData::new(
+                    bank.get_minimum_balance_for_rent_exemption(mint_data_size),
+                    mint_data_size,
+                    &token_program_id,
+                );
+                account.set_data(data);
+                bank.store_account(mint, &account);
+            }
+        }
+
+        // Create or update the token account so that it holds `amount`,
+        // preserving all other fields on an existing account.
+        match bank.get_account(token_account) {
+            Some(existing_account) if *existing_account.owner() == spl_token_2022::id() => {
+                // Patch the base `amount` in place, preserving TLV extensions.
+                let mut data = existing_account.data().to_vec();
+                {
+                    let mut state =
+                        StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut data)
+                            .map_err(|e| {
+                                Error::invalid_params(format!("Invalid token account data: {}", e))
+                            })?;
+                    state.base.amount = amount;
+                    state.pack_base();
+                }
+                let mut account = AccountSharedData::new(
+                    existing_account.lamports(),
+                    data.len(),
+                    existing_account.owner(),
+                );
+                account.set_data(data);
+                bank.store_account(token_account, &account);
+            }
+            existing => {
+                let account_data_size = TokenAccount::get_packed_len();
+                let (lamports, token_state) = match existing {
+                    Some(existing_account)
+                        if *existing_account.owner() == spl_token::id() =>
+                    {
+                        let mut token_data = TokenAccount::unpack(existing_account.data())
+                            .map_err(|e| {
+                                Error::invalid_params(format!(
+                                    "Invalid token account data: {}",
+                                    e
+                                ))
+                            })?;
+                        token_data.amount = amount;
+                        (existing_account.lamports(), token_data)
+                    }
+                    _ => (
+                        bank.get_minimum_balance_for_rent_exemption(account_data_size),
+                        TokenAccount {
+                            mint: *mint,
+                            owner: *owner,
+                            amount,
+                            delegate: None.into(),
+                            state: AccountState::Initialized,
+                            is_native: None.into(),
+                            delegated_amount: 0,
+                            close_authority: None.into(),
+                        },
+                    ),
+                };
+
+                let mut data = vec![0; account_data_size];
+                TokenAccount::pack(token_state, &mut data).map_err(|e| {
+                    Error::invalid_params(format!("Failed to pack token account data: {}", e))
+                })?;
+
+                let mut account =
+                    AccountSharedData::new(lamports, account_data_size, &token_program_id);
+                account.set_data(data);
+                bank.store_account(token_account, &account);
+            }
+        }
+
+        Ok(())
+    }
 
     pub fn set_account(&self, address: &Pubkey, account: &AccountSharedData) {
         let bank_forks = self.bank_forks.read().unwrap();
@@ -120,6 +665,83 @@ impl TestValidatorJsonRpcRequestProcessor {
         bank.store_account(address, account);
     }
 
+    // Set an account from an already-compressed, base64-encoded payload. `data`
+    // is the base64 blob produced by the `Base64`/`Base64Zstd` encodings and
+    // `encoding` selects the decoder; the decompressed length is validated
+    // against `data_len` (the account's stated `data.len()`) before storing.
+    pub fn set_account_compressed(
+        &self,
+        address: &Pubkey,
+        lamports: u64,
+        owner: &Pubkey,
+        executable: bool,
+        rent_epoch: solana_sdk::clock::Epoch,
+        data_len: usize,
+        data: &str,
+        encoding: solana_account_decoder::UiAccountEncoding,
+    ) -> Result<()> {
+        use solana_sdk::account::WritableAccount;
+
+        let decoded = decode_account_data(data, encoding)?;
+        if decoded.len() != data_len {
+            return Err(Error::invalid_params(format!(
+                "Decoded account data length {} does not match declared length {}",
+                decoded.len(),
+                data_len
+            )));
+        }
+
+        let mut account = AccountSharedData::new(lamports, data_len, owner);
+        account.set_data(decoded);
+        account.set_executable(executable);
+        account.set_rent_epoch(rent_epoch);
+
+        let bank_forks = self.bank_forks.read().unwrap();
+        let bank = bank_forks.working_bank();
+        bank.store_account(address, &account);
+
+        Ok(())
+    }
+
+    // Reconstruct an account from the native `UiAccount` shape emitted by the
+    // standard `getAccountInfo` RPC, so a user can copy an account straight from
+    // a block explorer or mainnet response into the test validator. Reverses
+    // `UiAccount::encode`: `owner` is parsed back to a `Pubkey`, the binary
+    // encodings (base58 / base64 / base64+zstd) are decoded, and `jsonParsed`
+    // token/stake/nonce payloads are re-packed via the account-decoder reverse.
+    pub fn set_account_from_ui_account(
+        &self,
+        address: &Pubkey,
+        ui_account: solana_account_decoder::UiAccount,
+    ) -> Result<()> {
+        use solana_account_decoder::UiAccountData;
+        use solana_sdk::account::WritableAccount;
+        use std::str::FromStr;
+
+        let owner = Pubkey::from_str(&ui_account.owner)
+            .map_err(|e| Error::invalid_params(format!("Invalid account owner: {}", e)))?;
+
+        let data = match ui_account.data {
+            UiAccountData::Binary(blob, encoding) => decode_account_data(&blob, encoding)?,
+            UiAccountData::LegacyBinary(blob) => decode_account_data(
+                &blob,
+                solana_account_decoder::UiAccountEncoding::Base58,
+            )?,
+            UiAccountData::Json(parsed) => repack_parsed_account(&parsed)?,
+        };
+
+        let mut account = AccountSharedData::new(ui_account.lamports, data.len(), &owner);
+        account.set_data(data);
+        account.set_executable(ui_account.executable);
+        account.set_rent_epoch(ui_account.rent_epoch);
+
+        let bank_forks = self.bank_forks.read().unwrap();
+        let bank = bank_forks.working_bank();
+        bank.store_account(address, &account);
+
+        Ok(())
+    }
+
     pub fn update_token_account_impl(
         &self,
         token_account: Option<&Pubkey>,
@@ -137,22 +759,32 @@ impl TestValidatorJsonRpcRequestProcessor {
             Some(pubkey) => *pubkey,
             None => {
                 // If no token_account provided, mint must be provided
-                let mint_pubkey = mint.ok_or_else(|| 
+                let mint_pubkey = mint.ok_or_else(||
                     Error::invalid_params("Either token_account or mint must be provided")
                 )?;
-                
+
                 // Create a new token account with a random address
                 let new_token_account = Pubkey::new_unique();
-                
+
                 // Owner must be provided when creating a new account
-                let owner_pubkey = owner.ok_or_else(|| 
+                let owner_pubkey = owner.ok_or_else(||
                     Error::invalid_params("Owner must be provided when creating a new token account")
                 )?;
-                
+
                 // Amount defaults to 0 for new accounts if not specified
                 let token_amount = amount.unwrap_or(0);
-                
-                // Create a new token account
+
+                // Infer the token program from the mint's owner so the staged
+                // account matches the flavor (classic vs Token-2022) of its mint.
+                let token_program_id = bank
+                    .get_account(mint_pubkey)
+                    .map(|mint_account| *mint_account.owner())
+                    .filter(is_known_spl_token_id)
+                    .unwrap_or_else(spl_token::id);
+
+                // The base account layout is shared between the two programs, so a
+                // freshly created account (which carries no TLV extensions) can be
+                // packed with the classic `Account` and simply reowned.
                 let account_data_size = TokenAccount::get_packed_len();
                 let token_state = TokenAccount {
                     mint: *mint_pubkey,
@@ -164,21 +796,21 @@ impl TestValidatorJsonRpcRequestProcessor {
                     delegated_amount: 0,
                     close_authority: None.into(),
                 };
-                
+
                 let mut data = vec![0; account_data_size];
                 TokenAccount::pack(token_state, &mut data).map_err(|e| {
                     Error::invalid_params(format!("Failed to pack token account data: {}", e))
                 })?;
-                
+
                 let mut account = AccountSharedData::new(
                     bank.get_minimum_balance_for_rent_exemption(account_data_size),
                     account_data_size,
-                    &spl_token::id(),
+                    &token_program_id,
                 );
                 account.set_data(data);
-                
+
                 bank.store_account(&new_token_account, &account);
-                
+
                 return Ok(new_token_account);
             }
         };
@@ -188,48 +820,68 @@ impl TestValidatorJsonRpcRequestProcessor {
             Error::invalid_params(format!("Token account {} not found", token_account_pubkey))
         })?;
         
-        // Check if it's a token account
-        if existing_account.owner() != &spl_token::id() {
+        // Check if it's a token account owned by either SPL Token program.
+        let token_program_id = *existing_account.owner();
+        if !is_known_spl_token_id(&token_program_id) {
             return Err(Error::invalid_params(format!(
                 "Account {} is not a token account", token_account_pubkey
             )));
         }
-        
-        // Try to unpack the existing token account data
-        let mut token_data = TokenAccount::unpack(&existing_account.data())
-            .map_err(|e| Error::invalid_params(format!("Invalid token account data: {}", e)))?;
-        
-        // Update fields if provided
-        if let Some(owner_pubkey) = owner {
-            token_data.owner = *owner_pubkey;
-        }
-        
-        if let Some(mint_pubkey) = mint {
-            token_data.mint = *mint_pubkey;
-        }
-        
-        if let Some(token_amount) = amount {
-            token_data.amount = token_amount;
-        }
-        
-        // Pack the updated token account data
-        let account_data_size = TokenAccount::get_packed_len();
-        let mut data = vec![0; account_data_size];
-        TokenAccount::pack(token_data, &mut data).map_err(|e| {
-            Error::invalid_params(format!("Failed to pack token account data: {}", e))
-        })?;
-        
-        // Create the updated account
+
         let mut account = AccountSharedData::new(
             existing_account.lamports(),
-            account_data_size,
-            &spl_token::id(),
+            existing_account.data().len(),
+            &token_program_id,
         );
-        account.set_data(data);
-        
+
+        if token_program_id == spl_token_2022::id() {
+            // Token-2022: patch the base `Account` fields in place while
+            // preserving the trailing account-type byte and TLV extension data
+            // (e.g. `TransferFeeAmount`, `MemoTransfer`) byte-for-byte.
+            let mut data = existing_account.data().to_vec();
+            {
+                let mut state =
+                    StateWithExtensionsMut::<spl_token_2022::state::Account>::unpack(&mut data)
+                        .map_err(|e| {
+                            Error::invalid_params(format!("Invalid token account data: {}", e))
+                        })?;
+                if let Some(owner_pubkey) = owner {
+                    state.base.owner = *owner_pubkey;
+                }
+                if let Some(mint_pubkey) = mint {
+                    state.base.mint = *mint_pubkey;
+                }
+                if let Some(token_amount) = amount {
+                    state.base.amount = token_amount;
+                }
+                state.pack_base();
+            }
+            account.set_data(data);
+        } else {
+            // Classic SPL Token: unpack, patch, and repack the fixed-size account.
+            let mut token_data = TokenAccount::unpack(&existing_account.data())
+                .map_err(|e| Error::invalid_params(format!("Invalid token account data: {}", e)))?;
+
+            if let Some(owner_pubkey) = owner {
+                token_data.owner = *owner_pubkey;
+            }
+            if let Some(mint_pubkey) = mint {
+                token_data.mint = *mint_pubkey;
+            }
+            if let Some(token_amount) = amount {
+                token_data.amount = token_amount;
+            }
+
+            let mut data = vec![0; TokenAccount::get_packed_len()];
+            TokenAccount::pack(token_data, &mut data).map_err(|e| {
+                Error::invalid_params(format!("Failed to pack token account data: {}", e))
+            })?;
+            account.set_data(data);
+        }
+
         // Store the updated account
         bank.store_account(&token_account_pubkey, &account);
-        
+
         Ok(token_account_pubkey)
     }
     
@@ -238,36 +890,106 @@ impl TestValidatorJsonRpcRequestProcessor {
         address: &Pubkey,
         url: Option<&str>,
     ) -> Result<()> {
-        use solana_client::rpc_client::RpcClient;  
+        // A single account is just a one-element batch; resolving upgradeable
+        // program dependencies is what makes a cloned program actually invokable.
+        self.clone_accounts_from_cluster_impl(&[*address], url, 1)
+    }
+
+    // Clone one or more accounts from a remote cluster into the working bank.
+    // All `addresses` are fetched in a single `get_multiple_accounts` round-trip
+    // rather than N serial calls. Executables owned by the upgradeable BPF
+    // loader also pull in their `UpgradeableLoaderState::Program`
+    // `programdata_address`, so a cloned program stays invokable. `follow_depth`
+    // bounds how many rounds of referenced accounts are resolved (0 clones only
+    // the explicitly requested accounts).
+    pub fn clone_accounts_from_cluster_impl(
+        &self,
+        addresses: &[Pubkey],
+        url: Option<&str>,
+        follow_depth: usize,
+    ) -> Result<()> {
+        use solana_account_decoder::UiAccountEncoding;
+        use solana_client::rpc_client::RpcClient;
+        use solana_rpc_client_api::config::RpcAccountInfoConfig;
         use solana_sdk::account::{ReadableAccount, WritableAccount};
-        
+        use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+        use std::collections::HashSet;
+
         // Default to mainnet-beta if no URL is provided
         let url = url.unwrap_or("https://api.mainnet-beta.solana.com");
-        
+
         // Create a blocking client for simplicity
         let client = RpcClient::new(url.to_string());
-        
-        // Fetch the account data from the remote cluster
-        let account = client.get_account(address)
-            .map_err(|err| Error::invalid_params(format!(
-                "Failed to fetch account from {}: {}", url, err
-            )))?;
-        
-        // Convert to AccountSharedData
-        let mut account_data = AccountSharedData::new(
-            account.lamports(),
-            account.data().len(),
-            account.owner(),
-        ); 
-        account_data.set_data(account.data().to_vec());
-        account_data.set_executable(account.executable());
-        account_data.set_rent_epoch(account.rent_epoch());
-        
-        // Store the account in the test validator
+
         let bank_forks = self.bank_forks.read().unwrap();
         let bank = bank_forks.working_bank();
-        bank.store_account(address, &account_data);
-        
+
+        let mut seen: HashSet<Pubkey> = HashSet::new();
+        let mut pending: Vec<Pubkey> = addresses.to_vec();
+        let mut depth = 0usize;
+
+        while !pending.is_empty() {
+            // Only fetch addresses we have not already cloned this call.
+            let batch: Vec<Pubkey> = pending
+                .drain(..)
+                .filter(|address| seen.insert(*address))
+                .collect();
+            if batch.is_empty() {
+                break;
+            }
+
+            // One round-trip for the whole batch. Request the payload
+            // zstd-compressed to save bandwidth on large program accounts; the
+            // client decompresses transparently before handing back `Account`.
+            let config = RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64Zstd),
+                ..RpcAccountInfoConfig::default()
+            };
+            let accounts = client
+                .get_multiple_accounts_with_config(&batch, config)
+                .map_err(|err| {
+                    Error::invalid_params(format!(
+                        "Failed to fetch accounts from {}: {}",
+                        url, err
+                    ))
+                })?
+                .value;
+
+            let mut next = Vec::new();
+            for (address, maybe_account) in batch.iter().zip(accounts) {
+                let Some(account) = maybe_account else {
+                    continue;
+                };
+
+                // Convert to AccountSharedData and store it.
+                let mut account_data = AccountSharedData::new(
+                    account.lamports(),
+                    account.data().len(),
+                    account.owner(),
+                );
+                account_data.set_data(account.data().to_vec());
+                account_data.set_executable(account.executable());
+                account_data.set_rent_epoch(account.rent_epoch());
+                bank.store_account(address, &account_data);
+
+                // Follow the bytecode of upgradeable programs.
+                if depth < follow_depth
+                    && account.executable()
+                    && account.owner() == &bpf_loader_upgradeable::id()
+                {
+                    if let Ok(UpgradeableLoaderState::Program {
+                        programdata_address,
+                    }) = bincode::deserialize(account.data())
+                    {
+                        next.push(programdata_address);
+                    }
+                }
+            }
+
+            pending = next;
+            depth += 1;
+        }
+
         Ok(())
     }
 
@@ -302,97 +1024,4 @@ impl TestValidatorJsonRpcRequestProcessor {
         bank.set_sysvar_for_tests(&clock);
     }
 
-    // pub fn update_token_balance(
-    //     &self,
-    //     token_account: &Pubkey,
-    //     mint: &Pubkey,
-    //     owner: &Pubkey,
-    //     amount: u64,
-    // ) -> Result<()> {
-    //     use solana_sdk::program_pack::Pack;
-    //     use solana_inline_spl::token::state::{Account as TokenAccount, Mint as TokenMint};
-        
-    //     let bank_forks = self.bank_forks.read().unwrap();
-    //     let bank = bank_forks.working_bank();
-        
-    //     // First, ensure the mint exists or create it
-    //     let mint_account = bank.get_account(mint);
-    //     if mint_account.is_none() {
-    //         // Create a default mint with decimals=6 (like USDC)
-    //         let mint_data_size = TokenMint::get_packed_len();
-    //         let mut mint_data = vec![0; mint_data_size];
-    //         let mint_state = TokenMint {
-    //             mint_authority: solana_program::program_option::COption::Some(*owner),
-    //             supply: amount,
-    //             decimals: 6, // USDC has 6 decimals
-    //             is_initialized: true,
-    //             freeze_authority: solana_program::program_option::COption::Some(*owner),
-    //         };
-    //         TokenMint::pack(mint_state, &mut mint_data).map_err(|e| {
-    //             Error::invalid_params(format!("Failed to pack mint data: {}", e))
-    //         })?;
-            
-    //         let mint_account = AccountSharedData::new(
-    //             bank.get_minimum_balance_for_rent_exemption(mint_data_size),
-    //             mint_data.len(),
-    //             &solana_inline_spl::token::id(),
-    //         );
-            
-    //         let mut mint_account = mint_account.set_data(mint_data);
-    //         bank.store_account(mint, &mint_account);
-    //     }
-        
-    //     // Now update or create the token account
-    //     let account_data_size = TokenAccount::get_packed_len();
-    //     let mut account_data = vec![0; account_data_size];
-        
-    //     // If the account already exists, preserve its state except for the amount
-    //     let token_account_data = if let Some(existing_account) = bank.get_account(token_account) {
-    //         if existing_account.owner() == &solana_inline_spl::token::id() {
-    //             let mut token_state = TokenAccount::unpack(&existing_account.data())
-    //                 .map_err(|e| Error::invalid_params(format!("Invalid token account data: {}", e)))?;
-    //             token_state.amount = amount;
-    //             token_state
-    //         } else {
-    //             // Create a new token account state
-    //             TokenAccount {
-    //                 mint: *mint,
-    //                 owner: *owner,
-    //                 amount,
-    //                 delegate: solana_program::program_option::COption::None,
-    //                 state: solana_inline_spl::token::state::AccountState::Initialized,
-    //                 is_native: solana_program::program_option::COption::None,
-    //                 delegated_amount: 0,
-    //                 close_authority: solana_program::program_option::COption::None,
-    //             }
-    //         }
-    //     } else {
-    //         // Create a new token account state
-    //         TokenAccount {
-    //             mint: *mint,
-    //             owner: *owner,
-    //             amount,
-    //             delegate: solana_program::program_option::COption::None,
-    //             state: solana_inline_spl::token::state::AccountState::Initialized,
-    //             is_native: solana_program::program_option::COption::None,
-    //             delegated_amount: 0,
-    //             close_authority: solana_program::program_option::COption::None,
-    //         }
-    //     };
-        
-    //     TokenAccount::pack(token_account_data, &mut account_data).map_err(|e| {
-    //         Error::invalid_params(format!("Failed to pack token account data: {}", e))
-    //     })?;
-        
-    //     let token_account_obj = AccountSharedData::new(
-    //         bank.get_minimum_balance_for_rent_exemption(account_data_size),
-    //         account_data.len(),
-    //         &solana_inline_spl::token::id(),
-    //     );
-        
-    //     let token_account_obj = token_account_obj.set_data(account_data);
-    //     bank.store_account(token_account, &token_account_obj);
-        
-    //     Ok(())
-    // }
 }